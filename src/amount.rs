@@ -0,0 +1,144 @@
+use std::{
+    fmt,
+    ops::{Add, Sub},
+    str::FromStr,
+};
+
+use serde::{de, Deserializer, Serializer};
+use thiserror::Error;
+
+/// The number of ten-thousandths in a single unit. All [`Amount`]s are
+/// stored scaled by this factor so that arithmetic is exact integer
+/// arithmetic rather than binary floating point
+const SCALE: i64 = 10_000;
+
+/// A monetary amount with exactly four fractional digits of precision,
+/// stored as an `i64` count of ten-thousandths of a unit. This avoids the
+/// rounding drift that `f64` accumulates over long transaction streams
+/// and guarantees byte-for-byte reproducible output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount(i64);
+
+/// Any error that arises while parsing an [`Amount`] from its CSV
+/// representation
+#[derive(Debug, Error, PartialEq)]
+pub enum Error {
+    #[error("\"{0}\" is not a valid amount")]
+    Invalid(String),
+    #[error("\"{0}\" has more than 4 decimal places")]
+    TooPrecise(String),
+}
+
+impl Amount {
+    /// The zero amount
+    pub const ZERO: Amount = Amount(0);
+}
+
+impl FromStr for Amount {
+    type Err = Error;
+
+    /// Parses an amount from a decimal string such as `"2.742"`, rejecting
+    /// anything with more than 4 digits after the decimal point
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (int_part, frac_part) = match s.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (s, ""),
+        };
+        if frac_part.len() > 4 {
+            return Err(Error::TooPrecise(s.to_owned()));
+        }
+        let negative = int_part.starts_with('-');
+        let int_value: i64 = int_part
+            .parse()
+            .map_err(|_| Error::Invalid(s.to_owned()))?;
+        let mut frac_value: i64 = if frac_part.is_empty() {
+            0
+        } else {
+            format!("{:0<4}", frac_part)
+                .parse()
+                .map_err(|_| Error::Invalid(s.to_owned()))?
+        };
+        if negative {
+            frac_value = -frac_value;
+        }
+        Ok(Amount(int_value * SCALE + frac_value))
+    }
+}
+
+impl fmt::Display for Amount {
+    /// Renders the amount with exactly 4 decimal places, e.g. `"2.7420"`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let magnitude = self.0.abs();
+        write!(f, "{}{}.{:04}", sign, magnitude / SCALE, magnitude % SCALE)
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Amount(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Amount(self.0 - rhs.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Amount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl serde::Serialize for Amount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Amount;
+
+    /// Test that amounts round-trip through their string representation
+    #[test]
+    fn parse_and_display() {
+        let amount: Amount = "2.742".parse().unwrap();
+        assert_eq!(amount.to_string(), "2.7420");
+    }
+
+    /// Test that amounts with more than 4 decimal places are rejected
+    #[test]
+    fn rejects_excess_precision() {
+        assert!("1.23456".parse::<Amount>().is_err());
+    }
+
+    /// Test that addition and subtraction are exact, with no drift
+    #[test]
+    fn exact_arithmetic() {
+        let a: Amount = "0.1".parse().unwrap();
+        let b: Amount = "0.2".parse().unwrap();
+        assert_eq!((a + b).to_string(), "0.3000");
+        assert_eq!((a + b - a).to_string(), "0.2000");
+    }
+
+    /// Test negative amounts
+    #[test]
+    fn negative_amount() {
+        let amount: Amount = "-0.5".parse().unwrap();
+        assert_eq!(amount.to_string(), "-0.5000");
+    }
+}
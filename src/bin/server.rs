@@ -0,0 +1,75 @@
+use std::{env, sync::Arc};
+
+use accounts_engine::{engine::Engine, transaction::Transaction};
+use csv::{ReaderBuilder, Trim};
+use futures::stream;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
+
+/// Handles a single client connection. Each newline-framed CSV line is
+/// applied to the shared engine as it arrives; a bare `dump` line instead
+/// writes the current account table back to the client
+async fn handle_connection(socket: TcpStream, engine: Arc<Mutex<Engine>>) {
+    let (reader, mut writer) = socket.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.eq_ignore_ascii_case("dump") {
+            let engine = engine.lock().await;
+            let mut bytes = Vec::new();
+            if engine.write_csv(&mut bytes).is_ok() {
+                let _ = writer.write_all(&bytes).await;
+            }
+            continue;
+        }
+        // deserialize the single record with no header, same trimming
+        // rules as the file-based CLI
+        let mut record_reader = ReaderBuilder::new()
+            .trim(Trim::All)
+            .has_headers(false)
+            .from_reader(line.as_bytes());
+        let Some(maybe_transaction) = record_reader.deserialize::<Transaction>().next() else {
+            continue;
+        };
+        let mut engine = engine.lock().await;
+        let errs = engine
+            .apply_transaction_stream(stream::once(async { maybe_transaction }))
+            .await;
+        for (entry, err) in errs {
+            let _ = writer
+                .write_all(format!("Entry {}: {}\n", entry, err).as_bytes())
+                .await;
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    let addr = args.get(1).map(String::as_str).unwrap_or("127.0.0.1:8080");
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to bind {}: {}", addr, e);
+            return;
+        }
+    };
+    println!("Listening for transactions on {}", addr);
+    let engine = Arc::new(Mutex::new(Engine::new()));
+    loop {
+        let (socket, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        tokio::spawn(handle_connection(socket, engine.clone()));
+    }
+}
@@ -0,0 +1,9 @@
+//! Engine-wide configuration for optional, non-default processing rules
+
+/// Configuration controlling optional accounts-engine behavior
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EngineConfig {
+    /// Whether a withdrawal may be disputed, in addition to deposits.
+    /// Default: `false`, only deposits are disputable
+    pub withdrawals_disputable: bool,
+}
@@ -1,21 +1,31 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io, thread,
+};
+
+use futures::{pin_mut, Stream, StreamExt};
 
 use crate::{
     account::{Account, Error},
+    config::EngineConfig,
     transaction::Transaction,
 };
 
+/// The per-client accounts and entry-indexed errors produced by a single
+/// shard in [`Engine::apply_transactions_sharded`]
+type ShardResult = (HashMap<u16, Account>, Vec<(usize, Error)>);
+
 /// A toy payments engine
 pub struct Engine {
     /// The accounts available for processing
     pub accounts: HashMap<u16, Account>,
+    /// The configuration governing optional processing rules
+    config: EngineConfig,
 }
 
 impl Engine {
     /// Applies a group of streamed transactions to their associated accounts.
-    /// This could easily be made async with actual stream futures to support
-    /// real socket streaming. Returns the errors that occurred and the entry
-    /// in which they occurred.
+    /// Returns the errors that occurred and the entry in which they occurred.
     ///
     /// # Arguments
     ///
@@ -28,6 +38,7 @@ impl Engine {
         // ones, because here we are interested in the errors. this is a
         // bit fancy and could also be done more simply, but this is how
         // I like to make use of functional programming
+        let config = self.config;
         transactions
             .enumerate()
             .map(|(entry, maybe_transaction)| {
@@ -40,7 +51,7 @@ impl Engine {
                             self.accounts
                                 .entry(transaction.client)
                                 .or_insert_with(|| Account::new(transaction.client))
-                                .apply_transaction(transaction)
+                                .apply_transaction(&transaction, &config)
                         }
                         Err(e) => Err(e.into()),
                     },
@@ -50,10 +61,317 @@ impl Engine {
             .collect()
     }
 
-    /// Creates a new accounts engine
+    /// Applies a stream of transactions to their associated accounts as they
+    /// arrive, rather than buffering the whole input into a `Vec` first.
+    /// This is the real socket-streaming counterpart to
+    /// [`Engine::apply_transactions`], and processes the stream in a single
+    /// pass without holding the input records in memory. Memory still grows
+    /// with the input: each account keeps every deposit and withdrawal it
+    /// has applied, and errors are collected until the stream ends.
+    /// Returns the errors that occurred and the entry in which they
+    /// occurred, same as `apply_transactions`; errors are not yielded
+    /// while the stream is still running.
+    ///
+    /// # Arguments
+    ///
+    /// `transactions`: A stream of transactions, as they arrive. Does not
+    /// need to be `Unpin`; it is pinned internally, so callers can pass
+    /// streams built from `async` blocks (e.g. `stream::once`) directly
+    pub async fn apply_transaction_stream<T>(&mut self, transactions: T) -> Vec<(usize, Error)>
+    where
+        T: Stream<Item = Result<Transaction, csv::Error>>,
+    {
+        let mut errs = Vec::new();
+        let config = self.config;
+        let transactions = transactions.enumerate();
+        pin_mut!(transactions);
+        while let Some((entry, maybe_transaction)) = transactions.next().await {
+            // add 1 because it references readable entries
+            let entry = entry + 1;
+            let res = match maybe_transaction {
+                Ok(transaction) => self
+                    .accounts
+                    .entry(transaction.client)
+                    .or_insert_with(|| Account::new(transaction.client))
+                    .apply_transaction(&transaction, &config),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = res {
+                errs.push((entry, e));
+            }
+        }
+        errs
+    }
+
+    /// Processes a collection of transactions in parallel by partitioning
+    /// them into `shards` buckets keyed by `client % shards`, then applies
+    /// each shard's ordered sub-stream on its own thread with its own
+    /// sub-engine, since accounts for different clients never interact and
+    /// disputes only ever reference same-client transactions. The resulting
+    /// account maps are merged back into this engine once every shard
+    /// finishes. Per-client ordering is preserved, because every
+    /// transaction for a given client lands in the same shard in input
+    /// order, so results are bit-identical to `apply_transactions` run
+    /// single-threaded. Errors are merged back into a single
+    /// `Vec<(usize, Error)>` sorted by original entry index, matching
+    /// `apply_transactions`.
+    ///
+    /// # Arguments
+    ///
+    /// `transactions`: Some container of transactions
+    /// `shards`: The number of worker shards to partition clients across
+    pub fn apply_transactions_sharded<T>(
+        &mut self,
+        transactions: T,
+        shards: usize,
+    ) -> Vec<(usize, Error)>
+    where
+        T: IntoIterator<Item = Result<Transaction, csv::Error>>,
+    {
+        let shards = shards.max(1);
+        // partition entries (1-indexed, matching apply_transactions) into
+        // per-shard buffers. malformed records have no client to shard on,
+        // so they are routed to shard 0
+        let mut buffers: Vec<Vec<(usize, Result<Transaction, csv::Error>)>> =
+            (0..shards).map(|_| Vec::new()).collect();
+        for (entry, maybe_transaction) in transactions.into_iter().enumerate() {
+            let shard = match &maybe_transaction {
+                Ok(transaction) => transaction.client as usize % shards,
+                Err(_) => 0,
+            };
+            // add 1 because it references readable entries
+            buffers[shard].push((entry + 1, maybe_transaction));
+        }
+        // hand each shard any accounts it already owns from earlier calls,
+        // keyed by the same `client % shards` rule as the incoming
+        // transactions. otherwise merging the shards back with `extend`
+        // would silently discard any client's prior state whenever that
+        // client reappears in this batch
+        let mut shard_accounts: Vec<HashMap<u16, Account>> =
+            (0..shards).map(|_| HashMap::new()).collect();
+        for (client, account) in self.accounts.drain() {
+            shard_accounts[client as usize % shards].insert(client, account);
+        }
+        // each shard now owns a disjoint set of clients, so its sub-engine
+        // can process its sub-stream independently on its own thread
+        let config = self.config;
+        let shard_results: Vec<ShardResult> = thread::scope(|scope| {
+            let handles: Vec<_> = buffers
+                .into_iter()
+                .zip(shard_accounts)
+                .map(|(buffer, accounts)| {
+                    scope.spawn(move || {
+                        let original_entries: Vec<usize> =
+                            buffer.iter().map(|(entry, _)| *entry).collect();
+                        let mut shard_engine = Engine { accounts, config };
+                        let errs = shard_engine
+                            .apply_transactions(buffer.into_iter().map(|(_, t)| t));
+                        // apply_transactions numbers entries starting from
+                        // 1 relative to this shard's sub-stream; translate
+                        // them back to the original indices
+                        let errs = errs
+                            .into_iter()
+                            .map(|(shard_entry, err)| (original_entries[shard_entry - 1], err))
+                            .collect();
+                        (shard_engine.accounts, errs)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut errs = Vec::new();
+        for (accounts, shard_errs) in shard_results {
+            // safe to merge with `extend`: every shard owns a disjoint set
+            // of clients, both for the incoming batch and for whatever
+            // was already in `self.accounts`
+            self.accounts.extend(accounts);
+            errs.extend(shard_errs);
+        }
+        errs.sort_by_key(|(entry, _)| *entry);
+        errs
+    }
+
+    /// Serializes all accounts as CSV, sorted by client id, so output is
+    /// a stable, diffable artifact instead of depending on `HashMap`
+    /// iteration order.
+    ///
+    /// # Arguments
+    ///
+    /// `w`: The writer to serialize the accounts into
+    pub fn write_csv<W: io::Write>(&self, w: W) -> csv::Result<()> {
+        // has_headers(false) because we write the header explicitly below,
+        // rather than letting the first `serialize` call derive one
+        let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(w);
+        writer.write_record(["client", "available", "held", "total", "locked"])?;
+        let sorted: BTreeMap<u16, &Account> = self.accounts.iter().map(|(id, a)| (*id, a)).collect();
+        for account in sorted.values() {
+            writer.serialize(account)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Creates a new accounts engine with the default configuration
     pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    /// Creates a new accounts engine with the given configuration
+    ///
+    /// # Arguments
+    ///
+    /// `config`: The configuration governing optional processing rules
+    pub fn with_config(config: EngineConfig) -> Self {
         Self {
             accounts: HashMap::new(),
+            config,
         }
     }
 }
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+
+    use crate::transaction::{Transaction, Type};
+
+    use super::Engine;
+
+    /// Test that a stream of transactions is applied incrementally, with
+    /// per-entry errors reported rather than aborting the stream
+    #[tokio::test]
+    async fn stream_reports_errors_per_entry() {
+        let mut engine = Engine::new();
+        let transactions = stream::iter(vec![
+            Ok(Transaction {
+                r#type: Type::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some("10.0".parse().unwrap()),
+            }),
+            Ok(Transaction {
+                r#type: Type::Withdrawal,
+                client: 1,
+                tx: 2,
+                amount: Some("100.0".parse().unwrap()),
+            }),
+            Ok(Transaction {
+                r#type: Type::Deposit,
+                client: 1,
+                tx: 3,
+                amount: Some("5.0".parse().unwrap()),
+            }),
+        ]);
+        let errs = engine.apply_transaction_stream(transactions).await;
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].0, 2);
+        let mut csv = Vec::new();
+        engine.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "client,available,held,total,locked\n1,15.0000,0.0000,15.0000,false\n"
+        );
+    }
+
+    /// Test that a `!Unpin` stream, such as one built around an `async`
+    /// block, can be applied without the caller pinning it first
+    #[tokio::test]
+    async fn stream_accepts_non_unpin_stream() {
+        let mut engine = Engine::new();
+        let transaction = Ok(Transaction {
+            r#type: Type::Deposit,
+            client: 1,
+            tx: 1,
+            amount: Some("1.0".parse().unwrap()),
+        });
+        let errs = engine
+            .apply_transaction_stream(stream::once(async { transaction }))
+            .await;
+        assert!(errs.is_empty());
+    }
+
+    fn deposit(client: u16, tx: u32, amount: &str) -> Result<Transaction, csv::Error> {
+        Ok(Transaction {
+            r#type: Type::Deposit,
+            client,
+            tx,
+            amount: Some(amount.parse().unwrap()),
+        })
+    }
+
+    fn withdrawal(client: u16, tx: u32, amount: &str) -> Result<Transaction, csv::Error> {
+        Ok(Transaction {
+            r#type: Type::Withdrawal,
+            client,
+            tx,
+            amount: Some(amount.parse().unwrap()),
+        })
+    }
+
+    /// Test that sharded processing produces the same result as
+    /// single-threaded processing, including across repeated calls that
+    /// touch the same client, rather than the merge overwriting prior
+    /// per-client state
+    #[test]
+    fn sharded_processing_matches_single_threaded() {
+        let mut sharded = Engine::new();
+        sharded.apply_transactions(vec![deposit(1, 1, "100.0")].into_iter());
+        sharded.apply_transactions_sharded(vec![deposit(1, 2, "50.0")], 4);
+
+        let mut sequential = Engine::new();
+        sequential.apply_transactions(
+            vec![deposit(1, 1, "100.0"), deposit(1, 2, "50.0")].into_iter(),
+        );
+
+        let mut sharded_csv = Vec::new();
+        sharded.write_csv(&mut sharded_csv).unwrap();
+        let mut sequential_csv = Vec::new();
+        sequential.write_csv(&mut sequential_csv).unwrap();
+        assert_eq!(sharded_csv, sequential_csv);
+    }
+
+    /// Test that errors from sharded processing are reported against
+    /// their original entry index and merged back sorted, even though
+    /// the underlying shards finish in an arbitrary order
+    #[test]
+    fn sharded_errors_sorted_by_entry() {
+        let mut engine = Engine::new();
+        let errs = engine.apply_transactions_sharded(
+            vec![
+                withdrawal(2, 1, "10.0"),
+                deposit(1, 2, "5.0"),
+                withdrawal(1, 3, "100.0"),
+            ],
+            4,
+        );
+        assert_eq!(
+            errs.iter().map(|(entry, _)| *entry).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    /// Test that `write_csv` renders a stable header and rows sorted by
+    /// client id, regardless of `HashMap` iteration order
+    #[test]
+    fn write_csv_is_sorted() {
+        let mut engine = Engine::new();
+        engine.apply_transactions(
+            vec![deposit(3, 1, "1.0"), deposit(1, 2, "2.0"), deposit(2, 3, "3.0")].into_iter(),
+        );
+        let mut csv = Vec::new();
+        engine.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "client,available,held,total,locked\n\
+             1,2.0000,0.0000,2.0000,false\n\
+             2,3.0000,0.0000,3.0000,false\n\
+             3,1.0000,0.0000,1.0000,false\n"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+//! A toy payments engine: applies a stream of transactions to per-client
+//! accounts and renders the resulting balances as CSV.
+
+pub mod account;
+pub mod amount;
+pub mod config;
+pub mod engine;
+pub mod transaction;
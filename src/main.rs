@@ -1,14 +1,8 @@
 use std::{env, io};
 
-use csv::{ReaderBuilder, Trim, Writer};
+use accounts_engine::{engine::Engine, transaction::Transaction};
+use csv::{ReaderBuilder, Trim};
 use fallible_iterator::FallibleIterator;
-use transaction::Transaction;
-
-use crate::engine::Engine;
-
-mod account;
-mod engine;
-mod transaction;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -39,7 +33,7 @@ fn main() {
     // create the engine
     let mut engine = Engine::new();
     // apply the transactions
-    let errs = engine.apply_transactions(&transactions);
+    let errs = engine.apply_transactions(transactions.into_iter().map(Ok));
     if !errs.is_empty() {
         eprintln!(
             "The following {} errors occurred while applying transactions:",
@@ -49,17 +43,8 @@ fn main() {
             eprintln!("Entry {}: {}", entry, err);
         }
     }
-    // generate the output CSV
-    let mut writer = Writer::from_writer(io::stdout());
-    // output all accounts
-    for (id, account) in &engine.accounts {
-        // print errors that may happen in serialization
-        if let Err(e) = writer.serialize(account) {
-            eprintln!("Failed to serialize account {} to CSV: {}", id, e);
-        }
-    }
-    // make sure all CSV is output to stdout
-    if let Err(e) = writer.flush() {
-        eprint!("Failed to write accounts to stdout: {}", e);
+    // generate the output CSV, sorted by client id
+    if let Err(e) = engine.write_csv(io::stdout()) {
+        eprintln!("Failed to write accounts to stdout: {}", e);
     }
 }
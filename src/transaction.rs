@@ -1,5 +1,7 @@
 use serde_derive::Deserialize;
 
+use crate::amount::Amount;
+
 /// The type of a transaction. Types are aliased because
 /// we assume they will be with this capitalization
 #[derive(Clone, Debug, Deserialize, PartialEq)]
@@ -31,7 +33,7 @@ pub struct Transaction {
     /// The transaction identifier, likely unique
     pub tx: u32,
     /// The amount involved in the transaction
-    pub amount: Option<f64>,
+    pub amount: Option<Amount>,
 }
 
 #[cfg(test)]